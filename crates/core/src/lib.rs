@@ -1,39 +1,141 @@
 //! Code Optimizer Core Engine
 //! Advanced pattern matching and configuration support!
+//! Supports both line-based (substring/regex) and tree-sitter AST-based rules.
 
+use regex::Regex;
+use rhai::{Engine, Scope, AST};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::path::Path;
+use tree_sitter::{Parser, Query, QueryCursor, Tree};
 
 /// Programming languages we support
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Language {
     JavaScript,
     Python,
     Rust,
 }
 
+impl Language {
+    /// The tree-sitter grammar backing this language.
+    fn tree_sitter_language(&self) -> tree_sitter::Language {
+        match self {
+            Language::JavaScript => tree_sitter_javascript::language(),
+            Language::Python => tree_sitter_python::language(),
+            Language::Rust => tree_sitter_rust::language(),
+        }
+    }
+}
+
 /// Pattern matching types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PatternType {
     Contains(String),           // Simple substring matching
-    Regex(String),             // Regex pattern (we'll simulate for now)
+    Regex(String),             // Regex pattern, compiled once at registration and cached on the rule
     StartsWith(String),        // Line starts with pattern
     EndsWith(String),          // Line ends with pattern
+    Query(String),              // tree-sitter S-expression query, matched structurally over the AST
+    Script(String),             // Rhai script, evaluated per line for conditional logic
+}
+
+/// An error produced while compiling a rule's pattern, tagged with which
+/// backend produced it.
+#[derive(Debug)]
+enum PatternCompileError {
+    Regex(regex::Error),
+    Query(tree_sitter::QueryError),
+    Script(Box<rhai::ParseError>),
+}
+
+impl fmt::Display for PatternCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternCompileError::Regex(err) => write!(f, "{err}"),
+            PatternCompileError::Query(err) => write!(f, "{err}"),
+            PatternCompileError::Script(err) => write!(f, "{err}"),
+        }
+    }
 }
 
 /// Configuration for the optimizer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OptimizerConfig {
+    #[serde(default)]
     pub enabled_rules: HashMap<String, bool>,
+    #[serde(default)]
     pub custom_rules: Vec<OptimizationRule>,
+    #[serde(default = "default_severity_filter")]
     pub severity_filter: Vec<Severity>,
 }
 
+fn default_severity_filter() -> Vec<Severity> {
+    vec![Severity::Info, Severity::Warning, Severity::Error]
+}
+
+/// Standard Levenshtein edit distance (insert/delete/substitute all cost 1),
+/// computed with a two-row rolling array instead of a full DP table.
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+    let mut current_row = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.iter().enumerate() {
+        current_row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution_cost = if a_char == b_char { 0 } else { 1 };
+            current_row[j + 1] = (previous_row[j + 1] + 1)
+                .min(current_row[j] + 1)
+                .min(previous_row[j] + substitution_cost);
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+
+    previous_row[b.len()]
+}
+
+/// The closest known rule name to `unknown`, if any is within roughly a
+/// third of the longer name's length. Ties are broken by name order.
+fn closest_rule_name(unknown: &str, known_names: &[&str]) -> Option<String> {
+    let mut sorted_names = known_names.to_vec();
+    sorted_names.sort_unstable();
+
+    let mut best: Option<(&str, usize)> = None;
+    for &name in &sorted_names {
+        let distance = levenshtein_distance(unknown, name);
+        let threshold = (unknown.len().max(name.len()) / 3).max(1);
+        if distance > threshold {
+            continue;
+        }
+        if best.is_none_or(|(_, best_distance)| distance < best_distance) {
+            best = Some((name, distance));
+        }
+    }
+
+    best.map(|(name, _)| name.to_string())
+}
+
+/// A rule paired with its compiled regex, tree-sitter query, or Rhai script
+/// (if any), so we only pay the compilation cost once per rule instead of
+/// once per line.
+struct CompiledRule {
+    rule: OptimizationRule,
+    regex: Option<Regex>,
+    query: Option<Query>,
+    script: Option<AST>,
+}
+
 /// The main brain of our code optimizer
 pub struct CodeOptimizer {
     name: String,
-    rules: Vec<OptimizationRule>,
+    rules: Vec<CompiledRule>,
+    custom_rules: Vec<CompiledRule>,
     config: OptimizerConfig,
+    script_engine: Engine,
 }
 
 /// Represents a single optimization suggestion
@@ -47,10 +149,29 @@ pub struct Optimization {
     pub explanation: String,
     pub severity: Severity,
     pub confidence: f32,  // 0.0 to 1.0
+    pub match_kind: MatchKind,
+}
+
+/// Which backend produced an `Optimization`. Tooling that auto-applies
+/// suggestions (like the CLI's `fix` command) needs this: only `Line`
+/// guarantees `suggested_code` is a literal in-line replacement of
+/// `original_code`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    /// From a substring/regex rule: `suggested_code` is a direct
+    /// replacement of `original_code` within the line.
+    Line,
+    /// From a tree-sitter `Query` rule: `original_code` is the captured
+    /// node's text and `suggested_code` is the rule's static replacement,
+    /// not necessarily a safe substring swap for the whole line.
+    Structural,
+    /// From a Rhai `Script` rule: `suggested_code` is only the script's
+    /// advisory suggestion, not a line replacement.
+    Script,
 }
 
 /// How important is this optimization?
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Severity {
     Info,       // Nice to have
     Warning,    // Should fix
@@ -59,7 +180,7 @@ pub enum Severity {
 }
 
 /// A rule that can find and fix code issues
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OptimizationRule {
     pub name: String,
     pub language: Language,
@@ -71,6 +192,12 @@ pub struct OptimizationRule {
     pub enabled: bool,
 }
 
+impl Default for OptimizerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OptimizerConfig {
     /// Create default configuration
     pub fn new() -> Self {
@@ -80,11 +207,11 @@ impl OptimizerConfig {
             severity_filter: vec![Severity::Info, Severity::Warning, Severity::Error],
         }
     }
-    
+
     /// Load configuration from a simulated config file
     pub fn from_config_string(config_str: &str) -> Self {
         let mut config = OptimizerConfig::new();
-        
+
         // Simple config parser (in real app, use TOML/JSON)
         for line in config_str.lines() {
             let line = line.trim();
@@ -96,40 +223,98 @@ impl OptimizerConfig {
                 config.enabled_rules.insert(rule_name, true);
             }
         }
-        
+
         config
     }
-    
+
+    /// Load configuration from a `.optimizer.toml` file, including any
+    /// `custom_rules` it defines.
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        toml::from_str(&contents).map_err(ConfigError::Toml)
+    }
+
+    /// Load configuration from a JSON config file, including any
+    /// `custom_rules` it defines.
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        serde_json::from_str(&contents).map_err(ConfigError::Json)
+    }
+
     /// Add a custom rule
     pub fn add_custom_rule(&mut self, rule: OptimizationRule) {
         self.custom_rules.push(rule);
     }
 }
 
+/// An error produced while loading an `OptimizerConfig` from disk.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "failed to read config file: {err}"),
+            ConfigError::Toml(err) => write!(f, "failed to parse TOML config: {err}"),
+            ConfigError::Json(err) => write!(f, "failed to parse JSON config: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Default for CodeOptimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CodeOptimizer {
     /// Create a new optimizer with default config
     pub fn new() -> Self {
         let mut optimizer = CodeOptimizer {
             name: "Advanced Code Optimizer".to_string(),
             rules: Vec::new(),
+            custom_rules: Vec::new(),
             config: OptimizerConfig::new(),
+            script_engine: Self::build_script_engine(),
         };
-        
+
         optimizer.add_built_in_rules();
+        optimizer.compile_custom_rules();
         optimizer
     }
-    
+
     /// Create optimizer with custom config
     pub fn with_config(config: OptimizerConfig) -> Self {
         let mut optimizer = CodeOptimizer {
             name: "Advanced Code Optimizer".to_string(),
             rules: Vec::new(),
+            custom_rules: Vec::new(),
             config,
+            script_engine: Self::build_script_engine(),
         };
-        
+
         optimizer.add_built_in_rules();
+        optimizer.compile_custom_rules();
+        optimizer.warn_about_unknown_rule_names();
         optimizer
     }
+
+    /// A Rhai engine with the small helper API (`matches`, `starts_with`,
+    /// `ends_with`) that `PatternType::Script` rules can call on the line
+    /// text, e.g. `line.matches("var ")`.
+    fn build_script_engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.register_fn("matches", |s: &str, pat: &str| s.contains(pat));
+        engine.register_fn("starts_with", |s: &str, pat: &str| s.starts_with(pat));
+        engine.register_fn("ends_with", |s: &str, pat: &str| s.ends_with(pat));
+        engine
+    }
     
     /// Show capabilities
     pub fn hello(&self) -> String {
@@ -140,21 +325,98 @@ impl CodeOptimizer {
                 self.name, total_rules, enabled_rules, self.config.custom_rules.len())
     }
     
+    /// Compile a rule's pattern, caching the `Regex`/`Query`/`AST` on
+    /// success so later matching never has to recompile it. Returns the
+    /// rule name alongside the parse error so callers can report exactly
+    /// which rule is broken.
+    fn compile_rule(
+        rule: OptimizationRule,
+        script_engine: &Engine,
+    ) -> Result<CompiledRule, (String, PatternCompileError)> {
+        let mut regex = None;
+        let mut query = None;
+        let mut script = None;
+
+        match &rule.pattern_type {
+            PatternType::Regex(pattern) => match Regex::new(pattern) {
+                Ok(compiled) => regex = Some(compiled),
+                Err(err) => return Err((rule.name.clone(), PatternCompileError::Regex(err))),
+            },
+            PatternType::Query(source) => {
+                match Query::new(rule.language.tree_sitter_language(), source) {
+                    Ok(compiled) => query = Some(compiled),
+                    Err(err) => return Err((rule.name.clone(), PatternCompileError::Query(err))),
+                }
+            }
+            PatternType::Script(source) => match script_engine.compile(source) {
+                Ok(compiled) => script = Some(compiled),
+                Err(err) => {
+                    return Err((rule.name.clone(), PatternCompileError::Script(Box::new(err))))
+                }
+            },
+            PatternType::Contains(_) | PatternType::StartsWith(_) | PatternType::EndsWith(_) => {}
+        }
+
+        Ok(CompiledRule { rule, regex, query, script })
+    }
+
+    /// Register a built-in rule, compiling its pattern once up front.
+    ///
+    /// Built-in patterns are authored in this file, so a parse failure here
+    /// is a bug in the crate, not bad user input - we panic with the rule
+    /// name and the underlying parse error to make that failure obvious.
+    fn push_built_in_rule(&mut self, rule: OptimizationRule) {
+        match Self::compile_rule(rule, &self.script_engine) {
+            Ok(compiled) => self.rules.push(compiled),
+            Err((name, err)) => panic!("built-in rule '{name}' has an invalid pattern: {err}"),
+        }
+    }
+
+    /// Compile every custom rule from the current config, caching the
+    /// result so `analyze_code` never recompiles a regex/query/script per
+    /// line.
+    ///
+    /// A custom rule with an invalid pattern is reported (rule name + the
+    /// parse error) and excluded rather than silently falling back to
+    /// substring matching.
+    fn compile_custom_rules(&mut self) {
+        let script_engine = &self.script_engine;
+        self.custom_rules = self
+            .config
+            .custom_rules
+            .iter()
+            .cloned()
+            .filter_map(|rule| match Self::compile_rule(rule, script_engine) {
+                Ok(compiled) => Some(compiled),
+                Err((name, err)) => {
+                    eprintln!("warning: rule '{name}': invalid pattern: {err}");
+                    None
+                }
+            })
+            .collect();
+    }
+
     /// Add built-in optimization rules with advanced patterns
     fn add_built_in_rules(&mut self) {
         // JavaScript rules
-        self.rules.push(OptimizationRule {
+        //
+        // A `Query` rule rather than `Contains("let ")` so it only fires on
+        // real `let` declarations, not on `let` appearing inside comments or
+        // string literals.
+        self.push_built_in_rule(OptimizationRule {
             name: "use-const".to_string(),
             language: Language::JavaScript,
-            pattern_type: PatternType::Contains("let ".to_string()),
-            replacement: "const ".to_string(),
+            pattern_type: PatternType::Query(
+                "(lexical_declaration \"let\" @keyword)".to_string(),
+            ),
+            replacement: "const".to_string(),
             explanation: "Use 'const' for variables that never change".to_string(),
             severity: Severity::Info,
             confidence: 0.8,
             enabled: true,
         });
         
-        self.rules.push(OptimizationRule {
+        self.push_built_in_rule(OptimizationRule {
             name: "arrow-function".to_string(),
             language: Language::JavaScript,
             pattern_type: PatternType::Contains("function(".to_string()),
@@ -166,7 +428,7 @@ impl CodeOptimizer {
         });
         
         // Python rules with advanced patterns
-        self.rules.push(OptimizationRule {
+        self.push_built_in_rule(OptimizationRule {
             name: "list-comprehension".to_string(),
             language: Language::Python,
             pattern_type: PatternType::Contains("for ".to_string()),
@@ -177,7 +439,7 @@ impl CodeOptimizer {
             enabled: true,
         });
         
-        self.rules.push(OptimizationRule {
+        self.push_built_in_rule(OptimizationRule {
             name: "pathlib-usage".to_string(),
             language: Language::Python,
             pattern_type: PatternType::Contains("os.path.".to_string()),
@@ -189,7 +451,7 @@ impl CodeOptimizer {
         });
         
         // Rust rules
-        self.rules.push(OptimizationRule {
+        self.push_built_in_rule(OptimizationRule {
             name: "clippy-style".to_string(),
             language: Language::Rust,
             pattern_type: PatternType::Contains(".clone()".to_string()),
@@ -202,60 +464,90 @@ impl CodeOptimizer {
     }
     
     /// Get rules that are currently enabled
-    fn get_enabled_rules(&self) -> Vec<&OptimizationRule> {
+    fn get_enabled_rules(&self) -> Vec<&CompiledRule> {
         let mut enabled_rules = Vec::new();
-        
+
         // Check built-in rules
-        for rule in &self.rules {
+        for compiled in &self.rules {
             let is_enabled = self.config.enabled_rules
-                .get(&rule.name)
-                .unwrap_or(&rule.enabled);
-            
+                .get(&compiled.rule.name)
+                .unwrap_or(&compiled.rule.enabled);
+
             if *is_enabled {
-                enabled_rules.push(rule);
+                enabled_rules.push(compiled);
             }
         }
-        
+
         // Add custom rules
-        for rule in &self.config.custom_rules {
-            if rule.enabled {
-                enabled_rules.push(rule);
+        for compiled in &self.custom_rules {
+            if compiled.rule.enabled {
+                enabled_rules.push(compiled);
             }
         }
-        
+
         enabled_rules
     }
-    
-    /// Advanced pattern matching
-    fn matches_pattern(&self, line: &str, pattern: &PatternType) -> bool {
-        match pattern {
+
+    /// Line-based pattern matching (substring/regex rules)
+    fn matches_pattern(&self, line: &str, compiled: &CompiledRule) -> bool {
+        match &compiled.rule.pattern_type {
             PatternType::Contains(text) => line.contains(text),
             PatternType::StartsWith(text) => line.trim_start().starts_with(text),
             PatternType::EndsWith(text) => line.trim_end().ends_with(text),
-            PatternType::Regex(pattern) => {
-                // Simplified regex - in real app use regex crate
-                if pattern == r"let\s+\w+\s*=" {
-                    line.contains("let ") && line.contains("=")
-                } else {
-                    line.contains(&pattern.replace(r"\s+", " "))
-                }
-            }
+            PatternType::Regex(_) => compiled
+                .regex
+                .as_ref()
+                .expect("regex rule without a compiled Regex")
+                .is_match(line),
+            PatternType::Query(_) => unreachable!("query rules are matched structurally, not per line"),
+            PatternType::Script(_) => unreachable!("script rules are evaluated via analyze_scripts"),
         }
     }
-    
+
     /// Advanced code analysis with configuration
     pub fn analyze_code(&self, code: &str, language: Language) -> Vec<Optimization> {
         let mut optimizations = Vec::new();
-        let lines: Vec<&str> = code.lines().collect();
-        
+
         let enabled_rules = self.get_enabled_rules();
-        let relevant_rules: Vec<_> = enabled_rules.iter()
-            .filter(|rule| rule.language == language)
+        let relevant_rules: Vec<&CompiledRule> = enabled_rules.into_iter()
+            .filter(|compiled| compiled.rule.language == language)
             .collect();
-        
-        for (line_number, line) in lines.iter().enumerate() {
-            for rule in &relevant_rules {
-                if self.matches_pattern(line, &rule.pattern_type) {
+
+        let (query_rules, rest): (Vec<_>, Vec<_>) = relevant_rules
+            .into_iter()
+            .partition(|compiled| matches!(compiled.rule.pattern_type, PatternType::Query(_)));
+        let (script_rules, line_rules): (Vec<_>, Vec<_>) = rest
+            .into_iter()
+            .partition(|compiled| matches!(compiled.rule.pattern_type, PatternType::Script(_)));
+
+        optimizations.extend(self.analyze_lines(code, &language, &line_rules));
+
+        if !query_rules.is_empty() {
+            optimizations.extend(self.analyze_tree(code, &language, &query_rules));
+        }
+
+        if !script_rules.is_empty() {
+            optimizations.extend(self.analyze_scripts(code, &language, &script_rules));
+        }
+
+        // Sort by confidence (highest first)
+        optimizations.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
+        optimizations
+    }
+
+    /// Run the substring/regex rules line by line
+    fn analyze_lines(
+        &self,
+        code: &str,
+        language: &Language,
+        rules: &[&CompiledRule],
+    ) -> Vec<Optimization> {
+        let mut optimizations = Vec::new();
+
+        for (line_number, line) in code.lines().enumerate() {
+            for compiled in rules {
+                if self.matches_pattern(line, compiled) {
+                    let rule = &compiled.rule;
                     // Check severity filter
                     if self.config.severity_filter.contains(&rule.severity) {
                         optimizations.push(Optimization {
@@ -263,23 +555,149 @@ impl CodeOptimizer {
                             language: language.clone(),
                             line_number: line_number + 1,
                             original_code: line.to_string(),
-                            suggested_code: self.apply_replacement(line, rule),
+                            suggested_code: self.apply_replacement(line, compiled),
                             explanation: rule.explanation.clone(),
                             severity: rule.severity.clone(),
                             confidence: rule.confidence,
+                            match_kind: MatchKind::Line,
                         });
                     }
                 }
             }
         }
-        
-        // Sort by confidence (highest first)
-        optimizations.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
+
         optimizations
     }
-    
+
+    /// Run the tree-sitter query rules structurally over the parsed AST,
+    /// so matches never fire inside comments/strings the way the line
+    /// scanner can.
+    fn analyze_tree(
+        &self,
+        code: &str,
+        language: &Language,
+        rules: &[&CompiledRule],
+    ) -> Vec<Optimization> {
+        let mut optimizations = Vec::new();
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(language.tree_sitter_language())
+            .expect("failed to load tree-sitter grammar");
+        let tree: Tree = match parser.parse(code, None) {
+            Some(tree) => tree,
+            None => return optimizations,
+        };
+
+        let source = code.as_bytes();
+        for compiled in rules {
+            let rule = &compiled.rule;
+            if !self.config.severity_filter.contains(&rule.severity) {
+                continue;
+            }
+            let query = compiled.query.as_ref().expect("query rule without a compiled Query");
+
+            let mut cursor = QueryCursor::new();
+            for query_match in cursor.matches(query, tree.root_node(), source) {
+                for capture in query_match.captures {
+                    let node = capture.node;
+                    let original_code = node.utf8_text(source).unwrap_or_default().to_string();
+                    optimizations.push(Optimization {
+                        rule_name: rule.name.clone(),
+                        language: language.clone(),
+                        line_number: node.start_position().row + 1,
+                        original_code,
+                        suggested_code: rule.replacement.clone(),
+                        explanation: rule.explanation.clone(),
+                        severity: rule.severity.clone(),
+                        confidence: rule.confidence,
+                        match_kind: MatchKind::Structural,
+                    });
+                }
+            }
+        }
+
+        optimizations
+    }
+
+    /// Run the Rhai script rules line by line. Each script sees the line
+    /// text, its 1-based line number, and the language, and returns either
+    /// `()` for no match or a map `{ suggestion, explanation, confidence }`.
+    fn analyze_scripts(
+        &self,
+        code: &str,
+        language: &Language,
+        rules: &[&CompiledRule],
+    ) -> Vec<Optimization> {
+        let mut optimizations = Vec::new();
+
+        for (line_number, line) in code.lines().enumerate() {
+            for compiled in rules {
+                let rule = &compiled.rule;
+                if !self.config.severity_filter.contains(&rule.severity) {
+                    continue;
+                }
+                let ast = compiled.script.as_ref().expect("script rule without a compiled AST");
+
+                let mut scope = Scope::new();
+                scope.push("line", line.to_string());
+                scope.push("line_number", (line_number + 1) as i64);
+                scope.push("language", format!("{:?}", language));
+
+                let result = match self.script_engine.eval_ast_with_scope::<rhai::Dynamic>(&mut scope, ast) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        eprintln!("warning: rule '{}': script error: {}", rule.name, err);
+                        continue;
+                    }
+                };
+
+                if result.is_unit() {
+                    continue;
+                }
+
+                let Some(map) = result.try_cast::<rhai::Map>() else {
+                    eprintln!(
+                        "warning: rule '{}': script must return () or a map, got something else",
+                        rule.name
+                    );
+                    continue;
+                };
+
+                let suggested_code = map
+                    .get("suggestion")
+                    .and_then(|v| v.clone().into_string().ok())
+                    .unwrap_or_else(|| line.to_string());
+                let explanation = map
+                    .get("explanation")
+                    .and_then(|v| v.clone().into_string().ok())
+                    .unwrap_or_else(|| rule.explanation.clone());
+                let confidence = map
+                    .get("confidence")
+                    .and_then(|v| v.as_float().ok())
+                    .map(|f| f as f32)
+                    .unwrap_or(rule.confidence);
+
+                optimizations.push(Optimization {
+                    rule_name: rule.name.clone(),
+                    language: language.clone(),
+                    line_number: line_number + 1,
+                    original_code: line.to_string(),
+                    suggested_code,
+                    explanation,
+                    severity: rule.severity.clone(),
+                    confidence,
+                    match_kind: MatchKind::Script,
+                });
+            }
+        }
+
+        optimizations
+    }
+
     /// Apply rule replacement
-    fn apply_replacement(&self, line: &str, rule: &OptimizationRule) -> String {
+    fn apply_replacement(&self, line: &str, compiled: &CompiledRule) -> String {
+        let rule = &compiled.rule;
         match &rule.pattern_type {
             PatternType::Contains(pattern) => {
                 line.replace(pattern, &rule.replacement)
@@ -291,13 +709,60 @@ impl CodeOptimizer {
                     line.to_string()
                 }
             },
-            _ => line.replace("pattern", &rule.replacement), // Simplified
+            PatternType::Regex(_) => compiled
+                .regex
+                .as_ref()
+                .expect("regex rule without a compiled Regex")
+                .replace_all(line, rule.replacement.as_str())
+                .into_owned(),
+            PatternType::EndsWith(_) => line.replace("pattern", &rule.replacement), // Simplified
+            PatternType::Query(_) => unreachable!("query rules are replaced via analyze_tree"),
+            PatternType::Script(_) => unreachable!("script rules are replaced via analyze_scripts"),
         }
     }
     
     /// Add configuration at runtime
     pub fn update_config(&mut self, config: OptimizerConfig) {
         self.config = config;
+        self.compile_custom_rules();
+        self.warn_about_unknown_rule_names();
+    }
+
+    /// Warn about any `enabled_rules` key that doesn't match a known rule
+    /// name, suggesting the closest known name by edit distance. Typos like
+    /// `use-cosnt` would otherwise be silently ignored by
+    /// `get_enabled_rules`.
+    fn warn_about_unknown_rule_names(&self) {
+        let known_names: Vec<&str> = self
+            .rules
+            .iter()
+            .chain(self.custom_rules.iter())
+            .map(|compiled| compiled.rule.name.as_str())
+            .collect();
+
+        for configured_name in self.config.enabled_rules.keys() {
+            if known_names.contains(&configured_name.as_str()) {
+                continue;
+            }
+            match closest_rule_name(configured_name, &known_names) {
+                Some(suggestion) => eprintln!(
+                    "warning: unknown rule '{configured_name}'; did you mean '{suggestion}'?"
+                ),
+                None => eprintln!("warning: unknown rule '{configured_name}'"),
+            }
+        }
+    }
+
+    /// Every built-in and custom rule, regardless of whether it's currently
+    /// enabled. Used by tooling like `list-rules` that wants the full
+    /// picture rather than just what `analyze_code` would apply. Custom
+    /// rules whose pattern failed to compile are not included.
+    pub fn all_rules(&self) -> Vec<&OptimizationRule> {
+        self.rules
+            .iter()
+            .chain(self.custom_rules.iter())
+            .map(|compiled| &compiled.rule)
+            .collect()
     }
 }
 
@@ -388,4 +853,217 @@ for item in items:
             println!("  ✨ Custom: {}", opt.explanation);
         }
     }
+
+    #[test]
+    fn test_regex_rule_with_capture_group_replacement() {
+        let mut config = OptimizerConfig::new();
+
+        let custom_rule = OptimizationRule {
+            name: "let-to-const".to_string(),
+            language: Language::JavaScript,
+            pattern_type: PatternType::Regex(r"let\s+(\w+)\s*=".to_string()),
+            replacement: "const $1 =".to_string(),
+            explanation: "Use 'const' for variables that never change".to_string(),
+            severity: Severity::Info,
+            confidence: 0.9,
+            enabled: true,
+        };
+
+        config.add_custom_rule(custom_rule);
+        let optimizer = CodeOptimizer::with_config(config);
+
+        let code = "let userName = 'John';";
+        let optimizations = optimizer.analyze_code(code, Language::JavaScript);
+
+        // The built-in `use-const` rule (a query on `let` declarations) also
+        // fires on this line, so look up our rule specifically instead of asserting on the
+        // whole vector's length.
+        println!("🔡 Regex test: {} optimizations found", optimizations.len());
+        let regex_match = optimizations
+            .iter()
+            .find(|opt| opt.rule_name == "let-to-const")
+            .expect("let-to-const rule should have matched");
+        assert_eq!(regex_match.suggested_code, "const userName = 'John';");
+    }
+
+    #[test]
+    fn test_query_rule_ignores_comments_and_strings() {
+        let mut config = OptimizerConfig::new();
+
+        let ast_rule = OptimizationRule {
+            name: "let-declaration-ast".to_string(),
+            language: Language::JavaScript,
+            pattern_type: PatternType::Query(
+                "(lexical_declaration \"let\" (variable_declarator name: (identifier) @name))"
+                    .to_string(),
+            ),
+            replacement: "const".to_string(),
+            explanation: "Structural 'let' binding found via tree-sitter".to_string(),
+            severity: Severity::Info,
+            confidence: 0.85,
+            enabled: true,
+        };
+
+        config.add_custom_rule(ast_rule);
+        let optimizer = CodeOptimizer::with_config(config);
+
+        let code = "// let fake = 1;\nlet real = 2;\nconst s = \"let trap = 3;\";\n";
+        let optimizations = optimizer.analyze_code(code, Language::JavaScript);
+
+        // Only the genuine `let` declaration on line 2 should match, for both
+        // our custom rule and the built-in `use-const` rule (also Query-based
+        // now) - neither the comment nor the string literal should fire.
+        println!("🌳 Query test: {} optimizations found", optimizations.len());
+        assert!(optimizations.iter().all(|opt| opt.line_number == 2));
+        assert_eq!(optimizations.len(), 2);
+
+        let custom = optimizations
+            .iter()
+            .find(|opt| opt.rule_name == "let-declaration-ast")
+            .expect("custom rule should have matched");
+        assert_eq!(custom.original_code, "real");
+
+        let built_in = optimizations
+            .iter()
+            .find(|opt| opt.rule_name == "use-const")
+            .expect("use-const rule should have matched");
+        assert_eq!(built_in.original_code, "let");
+        assert_eq!(built_in.suggested_code, "const");
+    }
+
+    #[test]
+    fn test_invalid_regex_rule_is_reported_and_skipped() {
+        let mut config = OptimizerConfig::new();
+
+        let broken_rule = OptimizationRule {
+            name: "broken-regex".to_string(),
+            language: Language::JavaScript,
+            pattern_type: PatternType::Regex(r"(unclosed".to_string()),
+            replacement: "n/a".to_string(),
+            explanation: "This rule can never compile".to_string(),
+            severity: Severity::Info,
+            confidence: 0.5,
+            enabled: true,
+        };
+
+        config.add_custom_rule(broken_rule);
+        let optimizer = CodeOptimizer::with_config(config);
+
+        // The invalid rule is dropped rather than silently falling back to
+        // substring matching, so it never produces optimizations.
+        let optimizations = optimizer.analyze_code("let x = 1;", Language::JavaScript);
+        assert!(optimizations.iter().all(|opt| opt.rule_name != "broken-regex"));
+    }
+
+    #[test]
+    fn test_config_from_toml_file() {
+        let toml_str = r#"
+            severity_filter = ["Info", "Warning", "Error"]
+
+            [[custom_rules]]
+            name = "no-var"
+            language = "JavaScript"
+            pattern_type = { Contains = "var " }
+            replacement = "let "
+            explanation = "Use 'let' instead of 'var' for block scoping"
+            severity = "Warning"
+            confidence = 0.9
+            enabled = true
+        "#;
+
+        let path = std::env::temp_dir().join("code_optimizer_test_config.toml");
+        fs::write(&path, toml_str).expect("failed to write test config");
+
+        let config = OptimizerConfig::from_toml_file(&path).expect("valid TOML config");
+        fs::remove_file(&path).ok();
+
+        assert_eq!(config.custom_rules.len(), 1);
+        assert_eq!(config.custom_rules[0].name, "no-var");
+    }
+
+    #[test]
+    fn test_config_from_json_file() {
+        let json_str = r#"{
+            "enabled_rules": { "use-const": false },
+            "custom_rules": [],
+            "severity_filter": ["Info"]
+        }"#;
+
+        let path = std::env::temp_dir().join("code_optimizer_test_config.json");
+        fs::write(&path, json_str).expect("failed to write test config");
+
+        let config = OptimizerConfig::from_json_file(&path).expect("valid JSON config");
+        fs::remove_file(&path).ok();
+
+        assert_eq!(config.enabled_rules.get("use-const"), Some(&false));
+    }
+
+    #[test]
+    fn test_script_rule_returns_suggestion_map() {
+        let mut config = OptimizerConfig::new();
+
+        let script_rule = OptimizationRule {
+            name: "append-to-comprehension".to_string(),
+            language: Language::Python,
+            pattern_type: PatternType::Script(
+                r#"
+                    if line.matches("append") {
+                        #{
+                            suggestion: "[...]",
+                            explanation: "Single append() in a loop body can become a list comprehension",
+                            confidence: 0.75
+                        }
+                    } else {
+                        ()
+                    }
+                "#
+                .to_string(),
+            ),
+            replacement: String::new(),
+            explanation: "default explanation, overridden by the script".to_string(),
+            severity: Severity::Info,
+            confidence: 0.5,
+            enabled: true,
+        };
+
+        config.add_custom_rule(script_rule);
+        let optimizer = CodeOptimizer::with_config(config);
+
+        let code = "result.append(item * 2)";
+        let optimizations = optimizer.analyze_code(code, Language::Python);
+
+        println!("📜 Script test: {} optimizations found", optimizations.len());
+        assert_eq!(optimizations.len(), 1);
+        assert_eq!(optimizations[0].suggested_code, "[...]");
+        assert_eq!(optimizations[0].confidence, 0.75);
+    }
+
+    #[test]
+    fn test_levenshtein_distance() {
+        assert_eq!(levenshtein_distance("use-const", "use-const"), 0);
+        assert_eq!(levenshtein_distance("use-cosnt", "use-const"), 2);
+        assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_closest_rule_name_suggests_typo_fix() {
+        let known = vec!["use-const", "arrow-function", "pathlib-usage"];
+        assert_eq!(
+            closest_rule_name("use-cosnt", &known),
+            Some("use-const".to_string())
+        );
+        assert_eq!(closest_rule_name("completely-unrelated-name", &known), None);
+    }
+
+    #[test]
+    fn test_unknown_rule_name_in_config_does_not_panic() {
+        let mut config = OptimizerConfig::new();
+        config.enabled_rules.insert("use-cosnt".to_string(), false);
+
+        // Just exercises the warning path (printed to stderr); the optimizer
+        // should still build normally with the typo'd key ignored.
+        let optimizer = CodeOptimizer::with_config(config);
+        let optimizations = optimizer.analyze_code("let x = 1;", Language::JavaScript);
+        assert!(!optimizations.is_empty());
+    }
 }
\ No newline at end of file
@@ -1,73 +1,332 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use code_optimizer_core::{CodeOptimizer, Language};
+use code_optimizer_core::{CodeOptimizer, Language, MatchKind, Optimization, OptimizerConfig};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    // path to code file
-    #[arg(required = true)]
-    file_path: PathBuf,
+    /// Path to a `.optimizer.toml`/`.optimizer.json` config file with custom rules
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Analyze a file and print optimization suggestions
+    Check {
+        /// Path to the source file to analyze
+        #[arg(required = true)]
+        file_path: PathBuf,
+    },
+    /// Apply optimizations at or above a confidence threshold
+    Fix {
+        /// Path to the source file to rewrite
+        #[arg(required = true)]
+        file_path: PathBuf,
+        /// Only apply optimizations with at least this confidence (0.0-1.0)
+        #[arg(long, default_value_t = 0.0)]
+        min_confidence: f32,
+        /// Print a unified diff instead of writing the file
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write a starter `.optimizer.toml` config file
+    Init {
+        /// Where to write the starter config
+        #[arg(long, default_value = ".optimizer.toml")]
+        path: PathBuf,
+    },
+    /// List every built-in and custom rule
+    ListRules,
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    let code_content = match fs::read_to_string(&cli.file_path) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!(
-                "Error: failed to read file '{}': {}",
-                cli.file_path.display(),
-                e
-            );
-            std::process::exit(1);
-        }
-    };
-    let language = match detect_language_from_path(&cli.file_path) {
-        Some(lang) => lang,
-        None => {
-            eprintln!(
-                "Error: Could not determine the programming language from the file extension."
-            );
-            eprintln!("Supported extensions are: .js, .ts, .py, .rs");
-            std::process::exit(1);
+    match &cli.command {
+        Command::Check { file_path } => check(file_path, cli.config.as_deref()),
+        Command::Fix {
+            file_path,
+            min_confidence,
+            dry_run,
+        } => fix(file_path, *min_confidence, *dry_run, cli.config.as_deref()),
+        Command::Init { path } => init(path),
+        Command::ListRules => list_rules(cli.config.as_deref()),
+    }
+}
+
+/// Build an optimizer, loading custom rules from `--config` if one was given.
+fn build_optimizer(config_path: Option<&Path>) -> CodeOptimizer {
+    match config_path {
+        None => CodeOptimizer::new(),
+        Some(path) => {
+            let config = load_config(path).unwrap_or_else(|e| {
+                eprintln!("Error: failed to load config '{}': {}", path.display(), e);
+                std::process::exit(1);
+            });
+            CodeOptimizer::with_config(config)
         }
-    };
-    let optimizer = CodeOptimizer::new();
+    }
+}
+
+/// Load a config file, picking TOML vs JSON by extension.
+fn load_config(path: &Path) -> Result<OptimizerConfig, String> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => OptimizerConfig::from_json_file(path).map_err(|e| e.to_string()),
+        _ => OptimizerConfig::from_toml_file(path).map_err(|e| e.to_string()),
+    }
+}
+
+fn read_source(file_path: &Path) -> String {
+    fs::read_to_string(file_path).unwrap_or_else(|e| {
+        eprintln!(
+            "Error: failed to read file '{}': {}",
+            file_path.display(),
+            e
+        );
+        std::process::exit(1);
+    })
+}
+
+fn language_for(file_path: &Path) -> Language {
+    detect_language_from_path(file_path).unwrap_or_else(|| {
+        eprintln!("Error: Could not determine the programming language from the file extension.");
+        eprintln!("Supported extensions are: .js, .ts, .py, .rs");
+        std::process::exit(1);
+    })
+}
+
+fn check(file_path: &Path, config_path: Option<&Path>) {
+    let code_content = read_source(file_path);
+    let language = language_for(file_path);
+    let optimizer = build_optimizer(config_path);
     let optimizations = optimizer.analyze_code(&code_content, language);
 
     if optimizations.is_empty() {
         println!(
             "✅ No optimization suggestions found for '{}'.",
-            cli.file_path.display()
+            file_path.display()
         );
     } else {
         println!(
             "🔍 Found {} potential optimizations in '{}':\n",
             optimizations.len(),
-            cli.file_path.display()
+            file_path.display()
         );
-        for opt in optimizations {
-            println!("--------------------------------------------------");
+        for opt in &optimizations {
+            print_optimization(opt);
+        }
+        println!("--------------------------------------------------");
+    }
+}
+
+fn print_optimization(opt: &Optimization) {
+    println!("--------------------------------------------------");
+    println!(
+        "🎯 Rule: '{}' (Confidence: {:.0}%)",
+        opt.rule_name,
+        opt.confidence * 100.0
+    );
+    println!("💡 Suggestion: {}", opt.explanation);
+    println!("📍 Location: Line {}", opt.line_number);
+    println!("   Original:   {}", opt.original_code.trim());
+    println!("   Suggested:  {}", opt.suggested_code.trim());
+    println!();
+}
+
+fn fix(file_path: &Path, min_confidence: f32, dry_run: bool, config_path: Option<&Path>) {
+    let code_content = read_source(file_path);
+    let language = language_for(file_path);
+    let optimizer = build_optimizer(config_path);
+    let optimizations = optimizer.analyze_code(&code_content, language);
+
+    let (fixed_content, applied, skipped) =
+        apply_fixes(&code_content, &optimizations, min_confidence);
+
+    if applied == 0 {
+        if skipped > 0 {
             println!(
-                "🎯 Rule: '{}' (Confidence: {:.0}%)",
-                opt.rule_name,
-                opt.confidence * 100.0
+                "⚠️  No auto-applicable optimizations at or above {:.0}% confidence for '{}', \
+                 but {} structural/script suggestion(s) were skipped - run `check` to see them \
+                 and apply by hand.",
+                min_confidence * 100.0,
+                file_path.display(),
+                skipped
+            );
+        } else {
+            println!(
+                "✅ No optimizations at or above {:.0}% confidence for '{}'.",
+                min_confidence * 100.0,
+                file_path.display()
             );
-            println!("💡 Suggestion: {}", opt.explanation);
-            println!("📍 Location: Line {}", opt.line_number);
-            println!("   Original:   {}", opt.original_code.trim());
-            println!("   Suggested:  {}", opt.suggested_code.trim());
-            println!();
         }
-        println!("--------------------------------------------------");
+        return;
+    }
+
+    let skipped_note = if skipped > 0 {
+        format!(", {skipped} structural/script suggestion(s) skipped - see `check`")
+    } else {
+        String::new()
+    };
+
+    if dry_run {
+        print_diff(file_path, &code_content, &fixed_content);
+        println!("{applied} applied{skipped_note}");
+    } else {
+        fs::write(file_path, &fixed_content).unwrap_or_else(|e| {
+            eprintln!(
+                "Error: failed to write file '{}': {}",
+                file_path.display(),
+                e
+            );
+            std::process::exit(1);
+        });
+        println!(
+            "✅ Applied {} optimization(s) to '{}'{}.",
+            applied,
+            file_path.display(),
+            skipped_note
+        );
+    }
+}
+
+/// Replace each optimization's `original_code` with its `suggested_code` on
+/// the matching line, for every optimization at or above `min_confidence`.
+/// Returns the fixed source, the number of optimizations applied, and the
+/// number skipped because they weren't `MatchKind::Line`.
+///
+/// Only `MatchKind::Line` optimizations are safe to auto-apply this way:
+/// `original_code`/`suggested_code` for `Structural`/`Script` matches are not
+/// whole-line replacements (see `Optimization::match_kind`), so applying them
+/// here would corrupt the file. Those are counted as skipped rather than
+/// applied; `fix` reports how many were skipped and `check` lists them for
+/// the user to apply by hand.
+fn apply_fixes(
+    code: &str,
+    optimizations: &[Optimization],
+    min_confidence: f32,
+) -> (String, usize, usize) {
+    let mut lines: Vec<String> = code.lines().map(str::to_string).collect();
+    let mut applied = 0;
+    let mut skipped = 0;
+
+    for opt in optimizations {
+        if opt.confidence < min_confidence {
+            continue;
+        }
+        if opt.match_kind != MatchKind::Line {
+            skipped += 1;
+            continue;
+        }
+        if let Some(line) = lines.get_mut(opt.line_number.saturating_sub(1)) {
+            if line.contains(&opt.original_code) {
+                *line = line.replacen(&opt.original_code, &opt.suggested_code, 1);
+                applied += 1;
+            }
+        }
+    }
+
+    let mut fixed = lines.join("\n");
+    if code.ends_with('\n') {
+        fixed.push('\n');
+    }
+    (fixed, applied, skipped)
+}
+
+fn print_diff(file_path: &Path, original: &str, fixed: &str) {
+    println!("--- {}", file_path.display());
+    println!("+++ {}", file_path.display());
+    for (line_number, (before, after)) in original.lines().zip(fixed.lines()).enumerate() {
+        if before != after {
+            println!("@@ -{0} +{0} @@", line_number + 1);
+            println!("-{before}");
+            println!("+{after}");
+        }
+    }
+}
+
+fn init(path: &Path) {
+    if path.exists() {
+        eprintln!("Error: '{}' already exists.", path.display());
+        std::process::exit(1);
+    }
+
+    // Match load_config's own TOML-vs-JSON extension check, so a freshly
+    // written config is never unreadable by the tool that wrote it.
+    let starter = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => {
+            r#"{
+  "enabled_rules": {
+    "use-const": false
+  },
+  "severity_filter": ["Info", "Warning", "Error"],
+  "custom_rules": [
+    {
+      "name": "no-var",
+      "language": "JavaScript",
+      "pattern_type": { "Contains": "var " },
+      "replacement": "let ",
+      "explanation": "Use 'let' instead of 'var' for block scoping",
+      "severity": "Warning",
+      "confidence": 0.9,
+      "enabled": false
+    }
+  ]
+}
+"#
+        }
+        _ => {
+            r#"# Code Optimizer configuration
+# Disable or enable built-in rules by name.
+# [enabled_rules]
+# use-const = false
+
+severity_filter = ["Info", "Warning", "Error"]
+
+# Add your own rules here.
+# [[custom_rules]]
+# name = "no-var"
+# language = "JavaScript"
+# pattern_type = { Contains = "var " }
+# replacement = "let "
+# explanation = "Use 'let' instead of 'var' for block scoping"
+# severity = "Warning"
+# confidence = 0.9
+# enabled = true
+"#
+        }
+    };
+
+    fs::write(path, starter).unwrap_or_else(|e| {
+        eprintln!("Error: failed to write '{}': {}", path.display(), e);
+        std::process::exit(1);
+    });
+    println!("✅ Wrote starter config to '{}'.", path.display());
+}
+
+fn list_rules(config_path: Option<&Path>) {
+    let optimizer = build_optimizer(config_path);
+    let rules = optimizer.all_rules();
+
+    println!("📋 {} rule(s) registered:\n", rules.len());
+    for rule in rules {
+        println!(
+            "- {} [{:?}] severity={:?} confidence={:.0}% enabled={}",
+            rule.name,
+            rule.language,
+            rule.severity,
+            rule.confidence * 100.0,
+            rule.enabled
+        );
     }
 }
 
-fn detect_language_from_path(path: &PathBuf) -> Option<Language> {
+fn detect_language_from_path(path: &Path) -> Option<Language> {
     let extension = path.extension()?.to_str()?;
 
     match extension {
@@ -77,3 +336,71 @@ fn detect_language_from_path(path: &PathBuf) -> Option<Language> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use code_optimizer_core::{Language, Severity};
+
+    fn line_opt(
+        rule_name: &str,
+        line_number: usize,
+        original: &str,
+        suggested: &str,
+        confidence: f32,
+    ) -> Optimization {
+        Optimization {
+            rule_name: rule_name.to_string(),
+            language: Language::JavaScript,
+            line_number,
+            original_code: original.to_string(),
+            suggested_code: suggested.to_string(),
+            explanation: "test".to_string(),
+            severity: Severity::Info,
+            confidence,
+            match_kind: MatchKind::Line,
+        }
+    }
+
+    #[test]
+    fn test_apply_fixes_replaces_line_based_optimizations() {
+        let code = "let x = 1;\nlet y = 2;\n";
+        let optimizations = vec![line_opt("use-const", 1, "let x = 1;", "const x = 1;", 0.8)];
+
+        let (fixed, applied, skipped) = apply_fixes(code, &optimizations, 0.0);
+
+        assert_eq!(applied, 1);
+        assert_eq!(skipped, 0);
+        assert_eq!(fixed, "const x = 1;\nlet y = 2;\n");
+    }
+
+    #[test]
+    fn test_apply_fixes_skips_optimizations_below_min_confidence() {
+        let code = "let x = 1;\n";
+        let optimizations = vec![line_opt("use-const", 1, "let x = 1;", "const x = 1;", 0.5)];
+
+        let (fixed, applied, skipped) = apply_fixes(code, &optimizations, 0.8);
+
+        assert_eq!(applied, 0);
+        assert_eq!(skipped, 0);
+        assert_eq!(fixed, code);
+    }
+
+    #[test]
+    fn test_apply_fixes_skips_non_line_match_kinds() {
+        let code = "let real = 2;\n";
+        let mut structural = line_opt("use-const", 1, "let", "const", 0.8);
+        structural.match_kind = MatchKind::Structural;
+        let mut script = line_opt("append-to-comprehension", 1, "let real = 2;", "[...]", 0.8);
+        script.match_kind = MatchKind::Script;
+
+        let (fixed, applied, skipped) = apply_fixes(code, &[structural, script], 0.0);
+
+        // Neither non-`Line` optimization should touch the file - applying
+        // them verbatim would corrupt it (see the doc comment on `apply_fixes`) -
+        // but both should be counted as skipped so the caller can report them.
+        assert_eq!(applied, 0);
+        assert_eq!(skipped, 2);
+        assert_eq!(fixed, code);
+    }
+}